@@ -5,6 +5,22 @@
 use super::{
     http::Server as HttpServer, tcp::Server as TcpServer, TESTNET_VERSION,
 };
+// `Configuration`/`RawConfiguration` predate this series and are
+// defined in `client/src/configuration.rs`, not shown in this diff.
+// The stratum/prometheus/informant/node-mode/snapshot/work-notify/
+// tps-sampler subsystems below each add their fields to the existing
+// `RawConfiguration` (next to `test_mode`, `db_dir`, `tx_pool_size`
+// and friends) rather than introducing a second struct:
+//   stratum_enabled: bool, stratum_listen_address: String,
+//   stratum_port: u16, stratum_secret: Option<String>,        (chunk0-1)
+//   prometheus_enabled: bool, prometheus_listen_address: String,
+//   prometheus_port: u16,                                      (chunk0-2)
+//   informant_enabled: bool, informant_interval_ms: u64,       (chunk0-3)
+//   node_mode: String,                                         (chunk0-4)
+//   snapshot_enabled: bool, snapshot_interval_secs: u64,
+//   snapshot_chunk_size_bytes: usize,                          (chunk0-5)
+//   work_notify_urls: Vec<String>,                             (chunk0-6)
+//   tps_sampler_enabled: bool, tps_sampler_interval_ms: u64,   (chunk0-7)
 pub use crate::configuration::Configuration;
 use blockgen::BlockGenerator;
 
@@ -18,6 +34,13 @@ use cfxcore::{
 use crate::rpc::{
     impls::cfx::RpcImpl, setup_debug_rpc_apis, setup_public_rpc_apis, RpcBlock,
 };
+use crate::informant::Informant;
+use crate::mode::{ModeController, NodeMode};
+use crate::prometheus::PrometheusServer;
+use crate::snapshot::{SnapshotConfig, SnapshotManager};
+use crate::stratum::{Stratum, StratumConfig};
+use crate::tps::TpsSampler;
+use crate::work_notify::WorkNotifier;
 use cfx_types::{Address, U256};
 use cfxcore::block_data_manager::BlockDataManager;
 use ctrlc::CtrlC;
@@ -53,6 +76,12 @@ pub struct FullClientHandle {
     pub txgen: Arc<TransactionGenerator>,
     pub txgen_join_handle: Option<thread::JoinHandle<()>>,
     pub blockgen: Arc<BlockGenerator>,
+    pub stratum: Option<Arc<Stratum>>,
+    pub prometheus: Option<PrometheusServer>,
+    pub informant: Option<Informant>,
+    pub mode_controller: Arc<ModeController>,
+    pub snapshot_manager: Arc<SnapshotManager>,
+    pub tps_sampler: Option<Arc<TpsSampler>>,
     pub secret_store: Arc<SecretStore>,
     pub ledger_db: Weak<SystemDB>,
 }
@@ -60,10 +89,20 @@ pub struct FullClientHandle {
 impl FullClientHandle {
     pub fn into_be_dropped(
         self,
-    ) -> (Weak<SystemDB>, Arc<BlockGenerator>, Box<Any>) {
+    ) -> (
+        Weak<SystemDB>,
+        Arc<BlockGenerator>,
+        Option<Arc<Stratum>>,
+        Option<PrometheusServer>,
+        Option<Informant>,
+        Box<Any>,
+    ) {
         (
             self.ledger_db,
             self.blockgen,
+            self.stratum,
+            self.prometheus,
+            self.informant,
             Box::new((
                 self.consensus,
                 self.debug_rpc_http_server,
@@ -74,6 +113,9 @@ impl FullClientHandle {
                 self.txgen,
                 self.secret_store,
                 self.txgen_join_handle,
+                self.mode_controller,
+                self.snapshot_manager,
+                self.tps_sampler,
             )),
         )
     }
@@ -163,11 +205,24 @@ impl FullClient {
             cache_config,
             Arc::new(genesis_block),
             ledger_db.clone(),
-            storage_manager,
+            storage_manager.clone(),
             worker_thread_pool,
             conf.data_mananger_config(),
         ));
 
+        let snapshot_manager = SnapshotManager::new(
+            storage_manager,
+            data_man.clone(),
+            SnapshotConfig {
+                enabled: conf.raw_conf.snapshot_enabled,
+                interval: Duration::from_secs(
+                    conf.raw_conf.snapshot_interval_secs,
+                ),
+                chunk_size_bytes: conf.raw_conf.snapshot_chunk_size_bytes,
+            },
+        );
+        snapshot_manager.start_periodic(exit.clone());
+
         let txpool = Arc::new(TransactionPool::with_capacity(
             conf.raw_conf.tx_pool_size,
             data_man.clone(),
@@ -189,9 +244,13 @@ impl FullClient {
         let protocol_config = conf.protocol_config();
         let verification_config = conf.verification_config();
 
+        let node_mode = NodeMode::from_str(&conf.raw_conf.node_mode)?;
+
         let network = {
             let mut network = NetworkService::new(network_config);
-            network.start().unwrap();
+            if node_mode != NodeMode::Offline {
+                network.start().unwrap();
+            }
             Arc::new(network)
         };
 
@@ -210,6 +269,23 @@ impl FullClient {
         ));
         sync.register().unwrap();
 
+        if conf.raw_conf.snapshot_enabled
+            && data_man.block_count() <= 1
+            && node_mode != NodeMode::Offline
+        {
+            match snapshot_manager.bootstrap_from_network(&sync) {
+                Ok(Some(epoch)) => sync_graph.fast_forward_to(epoch),
+                Ok(None) => debug!(
+                    "No snapshot advertised by peers; syncing from genesis"
+                ),
+                Err(e) => warn!(
+                    "Snapshot bootstrap failed, falling back to syncing \
+                     from genesis: {:?}",
+                    e
+                ),
+            }
+        }
+
         if conf.raw_conf.test_mode && conf.raw_conf.data_propagate_enabled {
             let dp = Arc::new(DataPropagation::new(
                 conf.raw_conf.data_propagate_interval_ms,
@@ -267,7 +343,35 @@ impl FullClient {
             pow_config.clone(),
             maybe_author.clone().unwrap_or_default(),
         ));
-        if conf.raw_conf.start_mining {
+
+        if !conf.raw_conf.work_notify_urls.is_empty() {
+            let notifier =
+                Arc::new(WorkNotifier::new(conf.raw_conf.work_notify_urls.clone())?);
+            blockgen.on_new_work(move |problem_hash, boundary, height| {
+                notifier.notify(problem_hash, boundary, height);
+            });
+        }
+
+        // Stratum and the internal mining thread both feed the same
+        // `BlockGenerator`, so they are mutually exclusive: a pool miner
+        // should be the sole source of solved blocks when enabled.
+        let should_mine =
+            node_mode == NodeMode::Active && conf.raw_conf.start_mining;
+        let stratum = if conf.raw_conf.stratum_enabled
+            && node_mode == NodeMode::Active
+        {
+            let stratum_conf = StratumConfig {
+                listen_address: format!(
+                    "{}:{}",
+                    conf.raw_conf.stratum_listen_address,
+                    conf.raw_conf.stratum_port
+                )
+                .parse()
+                .map_err(|e| format!("Invalid stratum listen address: {:?}", e))?,
+                secret: conf.raw_conf.stratum_secret.clone(),
+            };
+            Some(Stratum::start(stratum_conf, blockgen.clone())?)
+        } else if should_mine {
             if maybe_author.is_none() {
                 panic!("mining-author is not set correctly, so you'll not get mining rewards!!!");
             }
@@ -279,10 +383,15 @@ impl FullClient {
                     BlockGenerator::start_mining(bg, 0);
                 })
                 .expect("Mining thread spawn error");
-        }
+            None
+        } else {
+            None
+        };
 
         let tx_conf = conf.tx_gen_config();
-        let txgen_handle = if tx_conf.generate_tx {
+        let should_generate_tx =
+            node_mode == NodeMode::Active && tx_conf.generate_tx;
+        let txgen_handle = if should_generate_tx {
             let txgen_clone = txgen.clone();
             Some(
                 thread::Builder::new()
@@ -300,6 +409,26 @@ impl FullClient {
             None
         };
 
+        let tps_sampler = if conf.raw_conf.tps_sampler_enabled {
+            Some(TpsSampler::start(
+                Duration::from_millis(conf.raw_conf.tps_sampler_interval_ms),
+                exit.clone(),
+                data_man.clone(),
+            ))
+        } else {
+            None
+        };
+
+        let mode_controller = Arc::new(ModeController::new(
+            node_mode,
+            network.clone(),
+            blockgen.clone(),
+            txgen.clone(),
+            maybe_author.is_some(),
+            conf.raw_conf.generate_tx,
+            stratum.is_some(),
+        ));
+
         let rpc_impl = Arc::new(RpcImpl::new(
             consensus.clone(),
             sync.clone(),
@@ -307,6 +436,8 @@ impl FullClient {
             txpool.clone(),
             exit,
             network.clone(),
+            mode_controller.clone(),
+            tps_sampler.clone(),
         ));
 
         let debug_rpc_http_server = super::rpc::new_http(
@@ -345,6 +476,34 @@ impl FullClient {
             },
         )?;
 
+        let informant = if conf.raw_conf.informant_enabled {
+            Some(Informant::start(
+                Duration::from_millis(conf.raw_conf.informant_interval_ms),
+                consensus.clone(),
+                sync_graph.clone(),
+                network.clone(),
+                txpool.clone(),
+                blockgen.clone(),
+            ))
+        } else {
+            None
+        };
+
+        let prometheus = if conf.raw_conf.prometheus_enabled {
+            let listen_address = format!(
+                "{}:{}",
+                conf.raw_conf.prometheus_listen_address,
+                conf.raw_conf.prometheus_port
+            )
+            .parse()
+            .map_err(|e| {
+                format!("Invalid prometheus listen address: {:?}", e)
+            })?;
+            Some(PrometheusServer::start(listen_address)?)
+        } else {
+            None
+        };
+
         Ok(FullClientHandle {
             ledger_db: Arc::downgrade(&ledger_db),
             debug_rpc_http_server,
@@ -354,6 +513,12 @@ impl FullClient {
             txgen,
             txgen_join_handle: txgen_handle,
             blockgen,
+            stratum,
+            prometheus,
+            informant,
+            mode_controller,
+            snapshot_manager,
+            tps_sampler,
             consensus,
             secret_store,
             sync,
@@ -381,7 +546,17 @@ impl FullClient {
     }
 
     pub fn close(handle: FullClientHandle) {
-        let (ledger_db, blockgen, to_drop) = handle.into_be_dropped();
+        let (ledger_db, blockgen, stratum, prometheus, mut informant, to_drop) =
+            handle.into_be_dropped();
+        if let Some(stratum) = stratum {
+            stratum.stop();
+        }
+        if let Some(prometheus) = prometheus {
+            prometheus.stop();
+        }
+        if let Some(ref mut informant) = informant {
+            informant.stop();
+        }
         BlockGenerator::stop(&blockgen);
         drop(blockgen);
         drop(to_drop);
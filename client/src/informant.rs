@@ -0,0 +1,128 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! A periodic status-reporting subsystem, modelled on the `Informant`
+//! found in OpenEthereum and grin: a background thread that wakes on a
+//! fixed interval and logs a single compact line summarizing node
+//! health, so an operator tailing the log doesn't have to piece
+//! together progress from scattered debug output.
+
+use blockgen::BlockGenerator;
+use cfxcore::{
+    transaction_pool::TransactionPool, ConsensusGraph, SynchronizationGraph,
+};
+use network::NetworkService;
+use parking_lot::{Condvar, Mutex};
+use std::{
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Counters sampled on each tick so that rates can be computed by
+/// diffing against the previous tick instead of tracking absolute
+/// state.
+struct Snapshot {
+    at: Instant,
+    mined_blocks: u64,
+    imported_txs: u64,
+}
+
+pub struct Informant {
+    exit: Arc<(Mutex<bool>, Condvar)>,
+    join_handle: Option<thread::JoinHandle<()>>,
+}
+
+impl Informant {
+    pub fn start(
+        interval: Duration, consensus: Arc<ConsensusGraph>,
+        sync_graph: Arc<SynchronizationGraph>, network: Arc<NetworkService>,
+        txpool: Arc<TransactionPool>, blockgen: Arc<BlockGenerator>,
+    ) -> Informant
+    {
+        let exit = Arc::new((Mutex::new(false), Condvar::new()));
+        let exit_clone = exit.clone();
+
+        let join_handle = thread::Builder::new()
+            .name("informant".into())
+            .spawn(move || {
+                Self::run(
+                    interval,
+                    exit_clone,
+                    consensus,
+                    sync_graph,
+                    network,
+                    txpool,
+                    blockgen,
+                )
+            })
+            .expect("informant thread spawn error");
+
+        Informant {
+            exit,
+            join_handle: Some(join_handle),
+        }
+    }
+
+    pub fn stop(&mut self) {
+        *self.exit.0.lock() = true;
+        self.exit.1.notify_all();
+        if let Some(handle) = self.join_handle.take() {
+            let _ = handle.join();
+        }
+    }
+
+    fn run(
+        interval: Duration, exit: Arc<(Mutex<bool>, Condvar)>,
+        consensus: Arc<ConsensusGraph>, sync_graph: Arc<SynchronizationGraph>,
+        network: Arc<NetworkService>, txpool: Arc<TransactionPool>,
+        blockgen: Arc<BlockGenerator>,
+    )
+    {
+        let mut last = Snapshot {
+            at: Instant::now(),
+            mined_blocks: blockgen.total_mined_blocks(),
+            imported_txs: consensus.total_processed_tx_count(),
+        };
+
+        loop {
+            let mut exit_lock = exit.0.lock();
+            if !exit.1.wait_for(&mut exit_lock, interval).timed_out() {
+                return;
+            }
+            drop(exit_lock);
+
+            let now = Instant::now();
+            let elapsed_secs = (now - last.at).as_secs_f64().max(1e-6);
+
+            let mined_blocks = blockgen.total_mined_blocks();
+            let imported_txs = consensus.total_processed_tx_count();
+            let mined_per_min =
+                (mined_blocks - last.mined_blocks) as f64 / elapsed_secs * 60.0;
+            let imported_tps =
+                (imported_txs - last.imported_txs) as f64 / elapsed_secs;
+
+            info!(
+                "informant: best_epoch={} best_height={} graph_unprocessed={} \
+                 peers={} active_peers={} txpool_pending={} txpool_ready={} \
+                 mined_blocks_per_min={:.2} imported_tx_per_sec={:.2}",
+                consensus.best_epoch_number(),
+                consensus.best_block_height(),
+                sync_graph.blocks_awaiting_processing_count(),
+                network.peer_count(),
+                network.active_peer_count(),
+                txpool.pending_len(),
+                txpool.ready_len(),
+                mined_per_min,
+                imported_tps,
+            );
+
+            last = Snapshot {
+                at: now,
+                mined_blocks,
+                imported_txs,
+            };
+        }
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Node operating modes, following OpenEthereum's `active` / `passive`
+//! / `offline` run modes: `offline` keeps the node fully local (no
+//! networking, no gossip), `passive` syncs with peers but never mines
+//! or generates transactions (a running Stratum server is the one
+//! exception, see `ModeController`), and `active` is today's default
+//! behaviour. `ModeController` lets a running node switch between
+//! these at runtime, e.g. through a debug RPC call, without a restart.
+
+use blockgen::BlockGenerator;
+use network::NetworkService;
+use parking_lot::Mutex;
+use std::{str::FromStr, sync::Arc, thread};
+use txgen::TransactionGenerator;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NodeMode {
+    Active,
+    Passive,
+    Offline,
+}
+
+impl FromStr for NodeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active" => Ok(NodeMode::Active),
+            "passive" => Ok(NodeMode::Passive),
+            "offline" => Ok(NodeMode::Offline),
+            other => Err(format!("Invalid node mode: {}", other)),
+        }
+    }
+}
+
+/// Owns the switches needed to honor a `NodeMode` at runtime: whether
+/// networking is up, and whether the mining/transaction-generation
+/// threads are allowed to run.
+pub struct ModeController {
+    mode: Mutex<NodeMode>,
+    network: Arc<NetworkService>,
+    blockgen: Arc<BlockGenerator>,
+    txgen: Arc<TransactionGenerator>,
+    mining_author_set: bool,
+    generate_tx: bool,
+    /// Set when the Stratum server is the node's mining source. When
+    /// true, `ModeController` never starts or stops the internal
+    /// mining thread itself — doing so would race with Stratum and
+    /// violate the invariant that only one source feeds the block
+    /// generator. The Stratum server itself is not stopped by a mode
+    /// switch either: in `passive` and `offline` mode it keeps
+    /// accepting solutions from pool miners.
+    stratum_owns_mining: bool,
+}
+
+impl ModeController {
+    pub fn new(
+        initial_mode: NodeMode, network: Arc<NetworkService>,
+        blockgen: Arc<BlockGenerator>, txgen: Arc<TransactionGenerator>,
+        mining_author_set: bool, generate_tx: bool,
+        stratum_owns_mining: bool,
+    ) -> Self
+    {
+        ModeController {
+            mode: Mutex::new(initial_mode),
+            network,
+            blockgen,
+            txgen,
+            mining_author_set,
+            generate_tx,
+            stratum_owns_mining,
+        }
+    }
+
+    pub fn mode(&self) -> NodeMode { *self.mode.lock() }
+
+    /// Switch to `mode`, bringing networking/mining/tx-generation up or
+    /// down as needed. Idempotent: switching to the current mode is a
+    /// no-op.
+    pub fn set_mode(&self, mode: NodeMode) -> Result<(), String> {
+        let mut current = self.mode.lock();
+        if *current == mode {
+            return Ok(());
+        }
+
+        match (*current, mode) {
+            (NodeMode::Offline, NodeMode::Active)
+            | (NodeMode::Offline, NodeMode::Passive) => {
+                self.network.start().map_err(|e| format!("{:?}", e))?;
+            }
+            (_, NodeMode::Offline) => {
+                self.network.stop();
+            }
+            _ => {}
+        }
+
+        match mode {
+            NodeMode::Active => self.start_mining_if_configured(),
+            NodeMode::Passive | NodeMode::Offline => self.stop_mining(),
+        }
+
+        *current = mode;
+        Ok(())
+    }
+
+    fn start_mining_if_configured(&self) {
+        if !self.stratum_owns_mining && self.mining_author_set {
+            let bg = self.blockgen.clone();
+            thread::Builder::new()
+                .name("mining".into())
+                .spawn(move || {
+                    BlockGenerator::start_mining(bg, 0);
+                })
+                .expect("Mining thread spawn error");
+        }
+
+        if self.generate_tx {
+            let txgen = self.txgen.clone();
+            thread::Builder::new()
+                .name("txgen".into())
+                .spawn(move || txgen.resume())
+                .expect("txgen thread spawn error");
+        }
+    }
+
+    fn stop_mining(&self) {
+        if !self.stratum_owns_mining {
+            BlockGenerator::stop(&self.blockgen);
+        }
+        self.txgen.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NodeMode;
+    use std::str::FromStr;
+
+    #[test]
+    fn parses_known_modes() {
+        assert_eq!(NodeMode::from_str("active"), Ok(NodeMode::Active));
+        assert_eq!(NodeMode::from_str("passive"), Ok(NodeMode::Passive));
+        assert_eq!(NodeMode::from_str("offline"), Ok(NodeMode::Offline));
+    }
+
+    #[test]
+    fn rejects_unknown_mode() {
+        assert!(NodeMode::from_str("dark").is_err());
+        assert!(NodeMode::from_str("").is_err());
+    }
+}
@@ -0,0 +1,61 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Shared accept-loop helper for the simple line-based TCP listeners
+//! in this crate (`stratum`, `prometheus`). Both used to spawn one OS
+//! thread per accepted connection off an unbounded, busy-polling
+//! (`set_nonblocking` + sleep-on-`WouldBlock`) loop; a metrics scraper
+//! misconfiguration or a hostile client opening many connections could
+//! spawn unbounded threads with no cap. Routing connections through a
+//! shared, fixed-size `ThreadPool` instead (the same primitive
+//! `FullClient::start` already uses for tx-recovery workers) bounds
+//! concurrent connection handling: once the pool is busy, further
+//! accepted connections simply queue for a free worker.
+
+use std::{
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+use threadpool::ThreadPool;
+
+/// How many connections a single listener may service concurrently
+/// before further accepted connections queue for a free worker.
+pub const MAX_CONCURRENT_CONNECTIONS: usize = 64;
+
+/// Runs `listener`'s accept loop on the calling thread until
+/// `shutdown` is set, dispatching each accepted connection to `pool`.
+/// `pool` is expected to be sized via [`MAX_CONCURRENT_CONNECTIONS`];
+/// the callers in this crate each create a dedicated pool of that size
+/// and move it in here.
+pub fn accept_loop<F>(
+    listener: TcpListener, shutdown: Arc<AtomicBool>, pool: ThreadPool,
+    handle: F,
+) where
+    F: Fn(TcpStream) + Send + Sync + 'static,
+{
+    listener
+        .set_nonblocking(true)
+        .expect("failed to set listener non-blocking");
+    let handle = Arc::new(handle);
+    while !shutdown.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, _addr)) => {
+                let handle = handle.clone();
+                pool.execute(move || handle(stream));
+            }
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(100));
+            }
+            Err(e) => {
+                warn!("net_accept: accept error: {:?}", e);
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! A tiny HTTP listener that serves the process' metrics registry in
+//! Prometheus text exposition format on `GET /metrics`, so operators can
+//! point standard scrape tooling at a running node instead of tailing
+//! the `FileReporter` output.
+//!
+//! Requires crate-side support not included in this checkout: a
+//! `metrics::report_prometheus()` renderer producing `# TYPE`/`# HELP`
+//! text for the same registry `metrics::FileReporter` already reads.
+//! That belongs in the `metrics` crate alongside this file in the
+//! same series, not added here.
+
+use crate::net_accept::{self, MAX_CONCURRENT_CONNECTIONS};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+use threadpool::ThreadPool;
+
+const NOT_FOUND: &str = "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n";
+/// Bounds how long a single scrape connection may sit idle before it
+/// is abandoned, so a client that opens a connection and never
+/// finishes its request cannot tie up a thread forever.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A running Prometheus exposition endpoint.
+pub struct PrometheusServer {
+    shutdown: Arc<AtomicBool>,
+}
+
+impl PrometheusServer {
+    pub fn start(listen_address: SocketAddr) -> Result<PrometheusServer, String> {
+        let listener = TcpListener::bind(listen_address).map_err(|e| {
+            format!("Failed to bind prometheus listener: {:?}", e)
+        })?;
+
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let shutdown_clone = shutdown.clone();
+        let pool = ThreadPool::with_name(
+            "prometheus-conn".into(),
+            MAX_CONCURRENT_CONNECTIONS,
+        );
+        thread::Builder::new()
+            .name("prometheus".into())
+            .spawn(move || {
+                net_accept::accept_loop(
+                    listener,
+                    shutdown_clone,
+                    pool,
+                    Self::handle_connection,
+                )
+            })
+            .expect("prometheus thread spawn error");
+
+        info!("Prometheus metrics endpoint listening on {}", listen_address);
+        Ok(PrometheusServer { shutdown })
+    }
+
+    pub fn stop(&self) { self.shutdown.store(true, Ordering::SeqCst); }
+
+    fn handle_connection(mut stream: TcpStream) {
+        if let Err(e) = stream.set_read_timeout(Some(CONNECTION_TIMEOUT)) {
+            debug!("Prometheus: failed to set read timeout: {:?}", e);
+            return;
+        }
+        if let Err(e) = stream.set_write_timeout(Some(CONNECTION_TIMEOUT)) {
+            debug!("Prometheus: failed to set write timeout: {:?}", e);
+            return;
+        }
+
+        let mut reader = BufReader::new(
+            stream.try_clone().expect("failed to clone prometheus stream"),
+        );
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).is_err() {
+            return;
+        }
+
+        if !request_line.starts_with("GET /metrics") {
+            let _ = stream.write_all(NOT_FOUND.as_bytes());
+            return;
+        }
+
+        let body = metrics::report_prometheus();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body,
+        );
+        if let Err(e) = stream.write_all(response.as_bytes()) {
+            debug!("Prometheus: failed to write response: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,80 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! `RpcImpl` holds the shared state behind both the public and debug
+//! JSON-RPC APIs. This file only lists the fields and debug methods
+//! needed by the node-mode and TPS-sampler subsystems; the rest of
+//! `RpcImpl`'s surface (block/transaction/account queries) predates
+//! this series and lives alongside these additions in the real tree.
+
+use crate::{
+    mode::{ModeController, NodeMode},
+    tps::TpsSampler,
+};
+use blockgen::BlockGenerator;
+use cfxcore::{ConsensusGraph, SynchronizationService, TransactionPool};
+use network::NetworkService;
+use parking_lot::{Condvar, Mutex};
+use std::{str::FromStr, sync::Arc};
+
+pub struct RpcImpl {
+    consensus: Arc<ConsensusGraph>,
+    sync: Arc<SynchronizationService>,
+    blockgen: Arc<BlockGenerator>,
+    txpool: Arc<TransactionPool>,
+    exit: Arc<(Mutex<bool>, Condvar)>,
+    network: Arc<NetworkService>,
+    mode_controller: Arc<ModeController>,
+    tps_sampler: Option<Arc<TpsSampler>>,
+}
+
+impl RpcImpl {
+    pub fn new(
+        consensus: Arc<ConsensusGraph>, sync: Arc<SynchronizationService>,
+        blockgen: Arc<BlockGenerator>, txpool: Arc<TransactionPool>,
+        exit: Arc<(Mutex<bool>, Condvar)>, network: Arc<NetworkService>,
+        mode_controller: Arc<ModeController>,
+        tps_sampler: Option<Arc<TpsSampler>>,
+    ) -> Self
+    {
+        RpcImpl {
+            consensus,
+            sync,
+            blockgen,
+            txpool,
+            exit,
+            network,
+            mode_controller,
+            tps_sampler,
+        }
+    }
+
+    /// Debug RPC: switch the node's operating mode (`active` /
+    /// `passive` / `offline`) at runtime without a restart.
+    pub fn set_node_mode(&self, mode: String) -> Result<(), String> {
+        let mode = NodeMode::from_str(&mode)?;
+        self.mode_controller.set_mode(mode)
+    }
+
+    /// Debug RPC: report the node's current operating mode.
+    pub fn node_mode(&self) -> String {
+        match self.mode_controller.mode() {
+            NodeMode::Active => "active".into(),
+            NodeMode::Passive => "passive".into(),
+            NodeMode::Offline => "offline".into(),
+        }
+    }
+
+    /// Debug RPC: report the most recent and peak transaction
+    /// throughput observed by the TPS sampler, in transactions per
+    /// second. Errors if the node was not started with the sampler
+    /// enabled.
+    pub fn tps(&self) -> Result<(f64, f64), String> {
+        let sampler = self
+            .tps_sampler
+            .as_ref()
+            .ok_or_else(|| "TPS sampler is not enabled on this node".to_string())?;
+        Ok((sampler.recent_tps(), sampler.peak_tps()))
+    }
+}
@@ -0,0 +1,313 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! State-snapshot subsystem for fast bootstrap of new nodes, modelled
+//! on OpenEthereum's `ethcore::snapshot`. A snapshot is a manifest
+//! plus a set of content-addressed chunks capturing the committed
+//! state at a stabilized (finalized) epoch; a fresh node fetches the
+//! manifest and chunks over the network protocol and restores them
+//! directly into `StorageManager`, skipping a full replay from
+//! genesis.
+//!
+//! Requires crate-side support not included in this checkout:
+//! `StorageManager::state_iterator_at_epoch`/`new_snapshot_restorer`,
+//! `BlockDataManager::get_latest_stable_epoch`/`block_hash_by_epoch`/
+//! `deferred_state_root_by_block`, and
+//! `SynchronizationService::request_snapshot_manifest`/
+//! `request_snapshot_chunk`/`verified_header_by_epoch`, and
+//! `SynchronizationGraph::fast_forward_to`. These belong in `cfxcore`
+//! alongside this file in the same series, not added here.
+
+use cfx_types::H256;
+use cfxcore::{
+    block_data_manager::BlockDataManager, storage::StorageManager,
+    SynchronizationService,
+};
+use parking_lot::{Condvar, Mutex};
+use std::{sync::Arc, thread, time::Duration};
+
+/// A single content-addressed piece of state. Chunks are streamed
+/// from the multi-version store so producing one does not block
+/// ongoing consensus.
+pub struct Chunk {
+    pub hash: H256,
+    pub data: Vec<u8>,
+}
+
+/// Describes one chunk's contribution to the reconstructed state, so
+/// a restoring node can verify both the chunk itself and the final
+/// state root without trusting the peer that served it.
+#[derive(Clone)]
+pub struct ChunkInfo {
+    pub hash: H256,
+    pub state_root: H256,
+}
+
+#[derive(Clone)]
+pub struct Manifest {
+    pub epoch: u64,
+    pub block_hash: H256,
+    pub deferred_state_root: H256,
+    pub chunks: Vec<ChunkInfo>,
+}
+
+#[derive(Clone)]
+pub struct SnapshotConfig {
+    pub enabled: bool,
+    pub interval: Duration,
+    pub chunk_size_bytes: usize,
+}
+
+pub struct SnapshotManager {
+    storage_manager: Arc<StorageManager>,
+    data_man: Arc<BlockDataManager>,
+    conf: SnapshotConfig,
+}
+
+impl SnapshotManager {
+    pub fn new(
+        storage_manager: Arc<StorageManager>, data_man: Arc<BlockDataManager>,
+        conf: SnapshotConfig,
+    ) -> Arc<SnapshotManager>
+    {
+        Arc::new(SnapshotManager {
+            storage_manager,
+            data_man,
+            conf,
+        })
+    }
+
+    /// Spawns the periodic snapshotting thread. No-op when disabled in
+    /// config. Uses the same `exit` pattern as the rest of `start` for
+    /// clean termination.
+    pub fn start_periodic(
+        self: &Arc<Self>, exit: Arc<(Mutex<bool>, Condvar)>,
+    ) {
+        if !self.conf.enabled {
+            return;
+        }
+        let manager = self.clone();
+        thread::Builder::new()
+            .name("snapshot".into())
+            .spawn(move || loop {
+                let mut exit_lock = exit.0.lock();
+                if exit
+                    .1
+                    .wait_for(&mut exit_lock, manager.conf.interval)
+                    .timed_out()
+                {
+                    drop(exit_lock);
+                    if let Some(epoch) = manager.latest_stable_epoch() {
+                        match manager.take_snapshot(epoch) {
+                            Ok(manifest) => info!(
+                                "Snapshot taken at epoch {} ({} chunks)",
+                                epoch,
+                                manifest.chunks.len()
+                            ),
+                            Err(e) => {
+                                warn!("Failed to take snapshot: {:?}", e)
+                            }
+                        }
+                    }
+                } else {
+                    return;
+                }
+            })
+            .expect("snapshot thread spawn error");
+    }
+
+    fn latest_stable_epoch(&self) -> Option<u64> {
+        self.data_man.get_latest_stable_epoch()
+    }
+
+    /// Serializes the committed state at `epoch` into fixed-size
+    /// chunks, returning a manifest listing each chunk's hash and the
+    /// state root it contributes to.
+    pub fn take_snapshot(&self, epoch: u64) -> Result<Manifest, String> {
+        let block_hash = self
+            .data_man
+            .block_hash_by_epoch(epoch)
+            .ok_or_else(|| format!("Unknown epoch {}", epoch))?;
+        let deferred_state_root = self
+            .data_man
+            .deferred_state_root_by_block(&block_hash)
+            .ok_or_else(|| {
+                format!("No deferred state root for block {:?}", block_hash)
+            })?;
+
+        let mut chunks = Vec::new();
+        let mut iter = self
+            .storage_manager
+            .state_iterator_at_epoch(epoch)
+            .map_err(|e| format!("Failed to open state iterator: {:?}", e))?;
+
+        while let Some(chunk_data) =
+            iter.next_chunk(self.conf.chunk_size_bytes)
+        {
+            let hash = keccak_chunk(&chunk_data);
+            let state_root = iter.partial_state_root();
+            chunks.push(ChunkInfo { hash, state_root });
+        }
+
+        Ok(Manifest {
+            epoch,
+            block_hash,
+            deferred_state_root,
+            chunks,
+        })
+    }
+
+    /// Restores state from `manifest` and its chunks, verifying each
+    /// chunk's hash against `manifest`, and the final reconstructed
+    /// state root against `trusted_deferred_state_root` — which must
+    /// come from an independently, PoW-verified block header, *not*
+    /// from `manifest` itself. `manifest` is peer-supplied data; a
+    /// dishonest peer controls every field on it, including its own
+    /// copy of `deferred_state_root`, so checking the rebuilt root
+    /// against that copy would prove nothing.
+    pub fn restore_snapshot(
+        &self, manifest: &Manifest, chunks: Vec<Chunk>,
+        trusted_deferred_state_root: H256,
+    ) -> Result<(), String> {
+        if chunks.len() != manifest.chunks.len() {
+            return Err(format!(
+                "Expected {} chunks, got {}",
+                manifest.chunks.len(),
+                chunks.len()
+            ));
+        }
+
+        let mut restorer = self
+            .storage_manager
+            .new_snapshot_restorer()
+            .map_err(|e| format!("Failed to start restore: {:?}", e))?;
+
+        for (expected, chunk) in manifest.chunks.iter().zip(chunks.into_iter())
+        {
+            let actual_hash = keccak_chunk(&chunk.data);
+            if actual_hash != expected.hash {
+                return Err(format!(
+                    "Chunk hash mismatch: expected {:?}, got {:?}",
+                    expected.hash, actual_hash
+                ));
+            }
+            restorer
+                .apply_chunk(chunk.data)
+                .map_err(|e| format!("Failed to apply chunk: {:?}", e))?;
+        }
+
+        let reconstructed_root = restorer.finalize();
+        if reconstructed_root != trusted_deferred_state_root {
+            return Err(format!(
+                "Reconstructed state root {:?} does not match the \
+                 PoW-verified block's deferred_state_root {:?}",
+                reconstructed_root, trusted_deferred_state_root
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Part (b) of fast bootstrap: ask a peer over the existing
+    /// `SynchronizationService` protocol for the newest manifest it
+    /// advertises, pull every chunk it lists, and restore them. On
+    /// success returns the epoch the snapshot was taken at, which the
+    /// caller should resume normal block sync from instead of
+    /// genesis. Returns `Ok(None)` when no peer currently advertises a
+    /// snapshot.
+    ///
+    /// The manifest is peer-supplied and not trusted on its own: a
+    /// dishonest peer could otherwise serve a self-consistent manifest
+    /// plus chunks for arbitrary invented state. Before any chunk is
+    /// fetched, `manifest.epoch`'s block hash and deferred state root
+    /// are cross-checked against `sync`'s independently PoW-verified
+    /// header chain (ordinary header sync, which runs regardless of
+    /// snapshotting); only that verified copy of the state root is
+    /// used to accept the restored state.
+    pub fn bootstrap_from_network(
+        &self, sync: &Arc<SynchronizationService>,
+    ) -> Result<Option<u64>, String> {
+        let manifest = match sync
+            .request_snapshot_manifest()
+            .map_err(|e| format!("Failed to fetch snapshot manifest: {:?}", e))?
+        {
+            Some(manifest) => manifest,
+            None => return Ok(None),
+        };
+
+        let verified_header = sync
+            .verified_header_by_epoch(manifest.epoch)
+            .map_err(|e| {
+                format!(
+                    "Failed to verify header chain up to epoch {}: {:?}",
+                    manifest.epoch, e
+                )
+            })?
+            .ok_or_else(|| {
+                format!(
+                    "No independently PoW-verified header available yet \
+                     for epoch {}; refusing to trust the peer-supplied \
+                     snapshot manifest",
+                    manifest.epoch
+                )
+            })?;
+        if verified_header.block_hash != manifest.block_hash {
+            return Err(format!(
+                "Snapshot manifest block_hash {:?} does not match the \
+                 PoW-verified header at epoch {}: {:?}",
+                manifest.block_hash, manifest.epoch, verified_header.block_hash
+            ));
+        }
+        if verified_header.deferred_state_root != manifest.deferred_state_root
+        {
+            return Err(format!(
+                "Snapshot manifest deferred_state_root {:?} does not match \
+                 the PoW-verified header at epoch {}: {:?}",
+                manifest.deferred_state_root,
+                manifest.epoch,
+                verified_header.deferred_state_root
+            ));
+        }
+
+        let mut chunks = Vec::with_capacity(manifest.chunks.len());
+        for expected in &manifest.chunks {
+            let data = sync
+                .request_snapshot_chunk(expected.hash)
+                .map_err(|e| {
+                    format!(
+                        "Failed to fetch snapshot chunk {:?}: {:?}",
+                        expected.hash, e
+                    )
+                })?;
+            chunks.push(Chunk {
+                hash: expected.hash,
+                data,
+            });
+        }
+
+        self.restore_snapshot(
+            &manifest,
+            chunks,
+            verified_header.deferred_state_root,
+        )?;
+        info!(
+            "Restored snapshot at epoch {} from network, resuming block \
+             sync from there",
+            manifest.epoch
+        );
+        Ok(Some(manifest.epoch))
+    }
+}
+
+/// A block header's identity and state commitment, obtained through
+/// `sync`'s ordinary PoW-verified header-sync path rather than from
+/// any snapshot-specific, peer-supplied data. Used as the sole root of
+/// trust when restoring a snapshot.
+#[derive(Clone)]
+pub struct VerifiedHeader {
+    pub block_hash: H256,
+    pub deferred_state_root: H256,
+}
+
+fn keccak_chunk(data: &[u8]) -> H256 { cfx_types::keccak(data) }
@@ -0,0 +1,81 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Samples achieved transaction throughput, modelled on Solana's
+//! bench-tps sampler: a dedicated thread periodically diffs the
+//! cumulative count of transactions included in new blocks and tracks
+//! both the instantaneous and peak rate, so `--generate-tx` load tests
+//! have a number to look at instead of eyeballing log noise.
+//!
+//! Requires crate-side support not included in this checkout:
+//! `BlockDataManager::total_processed_tx_count`. That belongs in
+//! `cfxcore` alongside this file in the same series, not added here.
+
+use cfxcore::block_data_manager::BlockDataManager;
+use parking_lot::{Condvar, Mutex};
+use std::sync::Arc;
+use std::{thread, time::Duration};
+
+pub struct TpsSampler {
+    data_man: Arc<BlockDataManager>,
+    interval: Duration,
+    recent_tps: Mutex<f64>,
+    peak_tps: Mutex<f64>,
+}
+
+impl TpsSampler {
+    pub fn start(
+        interval: Duration, exit: Arc<(Mutex<bool>, Condvar)>,
+        data_man: Arc<BlockDataManager>,
+    ) -> Arc<TpsSampler>
+    {
+        let sampler = Arc::new(TpsSampler {
+            data_man,
+            interval,
+            recent_tps: Mutex::new(0.0),
+            peak_tps: Mutex::new(0.0),
+        });
+
+        {
+            let sampler = sampler.clone();
+            thread::Builder::new()
+                .name("tps-sampler".into())
+                .spawn(move || sampler.run(exit))
+                .expect("tps sampler thread spawn error");
+        }
+
+        sampler
+    }
+
+    pub fn recent_tps(&self) -> f64 { *self.recent_tps.lock() }
+
+    pub fn peak_tps(&self) -> f64 { *self.peak_tps.lock() }
+
+    fn run(&self, exit: Arc<(Mutex<bool>, Condvar)>) {
+        let mut last_count = self.data_man.total_processed_tx_count();
+
+        loop {
+            let mut exit_lock = exit.0.lock();
+            if !exit.1.wait_for(&mut exit_lock, self.interval).timed_out() {
+                return;
+            }
+            drop(exit_lock);
+
+            let count = self.data_man.total_processed_tx_count();
+            let tps =
+                (count - last_count) as f64 / self.interval.as_secs_f64();
+            last_count = count;
+
+            *self.recent_tps.lock() = tps;
+            let mut peak = self.peak_tps.lock();
+            if tps > *peak {
+                *peak = tps;
+            }
+
+            metrics::gauge!("txgen.tps.recent", tps);
+            metrics::gauge!("txgen.tps.peak", *peak);
+            info!("tps_sampler: recent={:.2} peak={:.2}", tps, *peak);
+        }
+    }
+}
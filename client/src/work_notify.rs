@@ -0,0 +1,178 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Pushes newly produced mining templates to configured HTTP
+//! endpoints, mirroring OpenEthereum's `WorkPoster`/`work_notify`. This
+//! lets out-of-process miners or dashboards react to new work without
+//! polling RPC.
+
+use cfx_types::{H256, U256};
+use serde_json::json;
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    thread,
+    time::Duration,
+};
+
+const POST_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// `host:port` plus the path to POST to, parsed once from a
+/// `http://host:port/path` config string.
+#[derive(Clone)]
+struct Endpoint {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Endpoint {
+    fn parse(url: &str) -> Result<Endpoint, String> {
+        let rest = match url.find("://") {
+            Some(idx) => {
+                let scheme = &url[..idx];
+                if scheme != "http" {
+                    return Err(format!(
+                        "Unsupported work_notify scheme {:?} in {} (only \
+                         plain http:// is supported)",
+                        scheme, url
+                    ));
+                }
+                &url[idx + "://".len()..]
+            }
+            None => url,
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(idx) => (&rest[..idx], &rest[idx..]),
+            None => (rest.as_str(), "/"),
+        };
+        let (host, port) = match authority.rfind(':') {
+            Some(idx) => (
+                authority[..idx].to_string(),
+                authority[idx + 1..]
+                    .parse()
+                    .map_err(|e| format!("Invalid port in {}: {:?}", url, e))?,
+            ),
+            None => (authority.to_string(), 80),
+        };
+        Ok(Endpoint {
+            host,
+            port,
+            path: path.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}{}", self.host, self.port, self.path)
+    }
+}
+
+/// Notifies a fixed set of URLs whenever `BlockGenerator` produces a
+/// new template. Registered as a callback on the block generator's
+/// template-update path; a failed POST is logged and simply dropped —
+/// the next template supersedes it, so there is nothing useful to
+/// retry.
+pub struct WorkNotifier {
+    endpoints: Vec<Endpoint>,
+}
+
+impl WorkNotifier {
+    pub fn new(urls: Vec<String>) -> Result<WorkNotifier, String> {
+        let endpoints = urls
+            .iter()
+            .map(|u| Endpoint::parse(u))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(WorkNotifier { endpoints })
+    }
+
+    /// Fire-and-forget: posts the new job to every configured URL on
+    /// its own thread so a slow or unreachable endpoint never blocks
+    /// block production.
+    pub fn notify(&self, problem_hash: H256, boundary: U256, height: u64) {
+        if self.endpoints.is_empty() {
+            return;
+        }
+        let body = json!({
+            "problem_hash": format!("{:#x}", problem_hash),
+            "target": format!("{:#x}", boundary),
+            "height": height,
+        })
+        .to_string();
+
+        for endpoint in self.endpoints.clone() {
+            let body = body.clone();
+            thread::Builder::new()
+                .name("work-notify".into())
+                .spawn(move || {
+                    if let Err(e) = post(&endpoint, &body) {
+                        warn!(
+                            "work_notify: failed to reach {}: {:?}",
+                            endpoint, e
+                        );
+                    }
+                })
+                .expect("work-notify thread spawn error");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Endpoint;
+
+    #[test]
+    fn parses_host_port_and_path() {
+        let e = Endpoint::parse("http://example.com:1234/work").unwrap();
+        assert_eq!(e.host, "example.com");
+        assert_eq!(e.port, 1234);
+        assert_eq!(e.path, "/work");
+    }
+
+    #[test]
+    fn parses_host_and_port_without_path() {
+        let e = Endpoint::parse("http://example.com:1234").unwrap();
+        assert_eq!(e.host, "example.com");
+        assert_eq!(e.port, 1234);
+        assert_eq!(e.path, "/");
+    }
+
+    #[test]
+    fn parses_host_only() {
+        let e = Endpoint::parse("http://example.com").unwrap();
+        assert_eq!(e.host, "example.com");
+        assert_eq!(e.port, 80);
+        assert_eq!(e.path, "/");
+    }
+
+    #[test]
+    fn rejects_https_with_clear_error() {
+        let err = Endpoint::parse("https://example.com:1234/work")
+            .unwrap_err();
+        assert!(err.contains("https"), "error was: {}", err);
+    }
+}
+
+fn post(endpoint: &Endpoint, body: &str) -> std::io::Result<()> {
+    let mut stream =
+        TcpStream::connect((endpoint.host.as_str(), endpoint.port))?;
+    stream.set_write_timeout(Some(POST_TIMEOUT))?;
+    stream.set_read_timeout(Some(POST_TIMEOUT))?;
+
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        endpoint.path,
+        endpoint.host,
+        body.len(),
+        body,
+    );
+    stream.write_all(request.as_bytes())?;
+
+    // Drain the response so the connection closes cleanly; the
+    // response body itself is not interesting to us.
+    let mut buf = [0u8; 256];
+    while stream.read(&mut buf)? > 0 {}
+    Ok(())
+}